@@ -1,18 +1,153 @@
-use std::str::FromStr;
+use std::{collections::HashMap, str::FromStr, sync::Arc};
 
+use async_trait::async_trait;
 use ethers::{
     prelude::*,
     types::{serde_helpers::StringifiedNumeric, transaction::eip2718::TypedTransaction},
 };
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
 use ethui_connections::Ctx;
 use ethui_dialogs::{Dialog, DialogMsg};
 use ethui_networks::Network;
 use ethui_settings::Settings;
-use ethui_types::{Address, GlobalState, ToAlloy, ToEthers};
+use ethui_types::{Address, GlobalState, ToAlloy, ToEthers, UINotify};
 use ethui_wallets::{WalletControl, WalletType, Wallets};
 
 use crate::{Error, Result};
 
+/// Minimum fee bump (12.5%) most nodes require to accept a replacement tx.
+const REPLACEMENT_BUMP: (u64, u64) = (1125, 1000);
+
+/// Provider wrapped with a local nonce tracker.
+type NonceProvider = NonceManagerMiddleware<Provider<RetryClient<Http>>>;
+
+/// A broadcast transaction that may later be replaced — sped up or cancelled —
+/// by re-sending at the same nonce with escalated fees.
+#[derive(Debug, Clone)]
+pub struct PendingTx {
+    pub hash: H256,
+    pub nonce: U256,
+    pub max_fee_per_gas: Option<U256>,
+    pub max_priority_fee_per_gas: Option<U256>,
+    pub gas_price: Option<U256>,
+}
+
+/// Nonce managers shared across every [`SendTransaction`], keyed per
+/// `(wallet address, chain_id)`.
+///
+/// The node is only queried once per key; after that each send increments the
+/// nonce locally, so a dApp firing several transactions back-to-back gets
+/// monotonically increasing nonces instead of colliding on the node's stale
+/// value. A failed send drops the cached manager so the next one resyncs from
+/// the node.
+static NONCE_MANAGERS: Lazy<Mutex<HashMap<(Address, u64), Arc<NonceProvider>>>> =
+    Lazy::new(Default::default);
+
+/// `max_fee_per_gas` suggestions at three confirmation speeds, in wei.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct GasTiers {
+    pub slow: U256,
+    pub standard: U256,
+    pub fast: U256,
+}
+
+impl GasTiers {
+    /// Spreads a single gas price into slow/standard/fast tiers.
+    fn from_base(base: U256) -> Self {
+        Self {
+            slow: base,
+            standard: base * 115 / 100,
+            fast: base * 130 / 100,
+        }
+    }
+
+    fn get(&self, tier: GasTier) -> U256 {
+        match tier {
+            GasTier::Slow => self.slow,
+            GasTier::Standard => self.standard,
+            GasTier::Fast => self.fast,
+        }
+    }
+}
+
+/// Confirmation-speed tier the user picks in the review dialog.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GasTier {
+    Slow,
+    #[default]
+    Standard,
+    Fast,
+}
+
+/// A source of gas-price suggestions, mirroring ethers' gas-oracle design so
+/// backends can be swapped or chained behind a common interface.
+///
+/// `estimate` returns `None` when the backend is unavailable, signalling a
+/// [`FallbackOracle`] to try the next source.
+#[async_trait]
+pub trait GasOracle: Send + Sync {
+    async fn estimate(&self) -> Option<GasTiers>;
+}
+
+/// Derives tiers from the RPC node's `eth_gasPrice`.
+pub struct NodeOracle {
+    provider: Provider<RetryClient<Http>>,
+}
+
+#[async_trait]
+impl GasOracle for NodeOracle {
+    async fn estimate(&self) -> Option<GasTiers> {
+        let price = self.provider.get_gas_price().await.ok()?;
+        Some(GasTiers::from_base(price))
+    }
+}
+
+/// Reads fast/standard/slow tiers (in gwei) from an external HTTP oracle.
+pub struct HttpOracle {
+    url: String,
+}
+
+#[derive(Deserialize)]
+struct HttpTiers {
+    fast: f64,
+    standard: f64,
+    slow: f64,
+}
+
+#[async_trait]
+impl GasOracle for HttpOracle {
+    async fn estimate(&self) -> Option<GasTiers> {
+        let tiers: HttpTiers = reqwest::get(&self.url).await.ok()?.json().await.ok()?;
+
+        let gwei = |v: f64| U256::from((v * 1e9) as u64);
+        Some(GasTiers {
+            slow: gwei(tiers.slow),
+            standard: gwei(tiers.standard),
+            fast: gwei(tiers.fast),
+        })
+    }
+}
+
+/// Tries a primary oracle, falling back to the node when it's unavailable.
+pub struct FallbackOracle {
+    primary: Box<dyn GasOracle>,
+    fallback: Box<dyn GasOracle>,
+}
+
+#[async_trait]
+impl GasOracle for FallbackOracle {
+    async fn estimate(&self) -> Option<GasTiers> {
+        match self.primary.estimate().await {
+            Some(tiers) => Some(tiers),
+            None => self.fallback.estimate().await,
+        }
+    }
+}
+
 /// Orchestrates the signing of a transaction
 /// Takes references to both the wallet and network where this
 #[derive(Debug)]
@@ -22,7 +157,17 @@ pub struct SendTransaction {
     pub wallet_path: String,
     pub wallet_type: WalletType,
     pub request: TypedTransaction,
-    pub signer: Option<SignerMiddleware<Provider<RetryClient<Http>>, ethui_wallets::Signer>>,
+    /// Gas reported by `eth_createAccessList`, used as a sharper estimate than
+    /// the flat 120% bump when an access list has been prefetched.
+    pub access_list_gas: Option<U256>,
+    /// The last transaction broadcast by this orchestrator, tracked so it can
+    /// be sped up or cancelled while still pending.
+    pub pending: Option<PendingTx>,
+    /// Confirmation-speed tier chosen in the review dialog.
+    pub gas_tier: GasTier,
+    /// Gas-price tiers last fetched from the configured oracle.
+    pub gas_tiers: Option<GasTiers>,
+    pub signer: Option<SignerMiddleware<Arc<NonceProvider>, ethui_wallets::Signer>>,
 }
 
 impl<'a> SendTransaction {
@@ -34,18 +179,204 @@ impl<'a> SendTransaction {
         // TODO: we're defaulting to 1_000_000 gas cost if estimation fails
         // estimation failing means the tx will faill anyway, so this is fine'ish
         // but can probably be improved a lot in the future
-        let gas_limit = self
+        let gas_limit = match self.access_list_gas {
+            // `eth_createAccessList` already gave us a tighter estimate, but its
+            // `gasUsed` is still state-dependent, so keep a small margin to avoid
+            // out-of-gas reverts — smaller than the blind 120% bump below
+            Some(gas) => gas * 110 / 100,
+            None => {
+                self.network
+                    .get_provider()
+                    .estimate_gas(&self.request, None)
+                    .await
+                    .unwrap_or(1_000_000.into())
+                    * 120
+                    / 100
+            }
+        };
+
+        self.request.set_gas(gas_limit);
+        self
+    }
+
+    /// Prefetches an EIP-2930 access list for the current request.
+    ///
+    /// Calls `eth_createAccessList`, stores the returned list on the
+    /// transaction so the node warms the same storage slots, and keeps the
+    /// reported `gasUsed` around as a sharper input to [`estimate_gas`]. Users
+    /// on chains where access lists don't help can disable this from settings.
+    pub async fn prefetch_access_list(&mut self) -> &mut SendTransaction {
+        if !Settings::read().await.access_list_prefetch() {
+            return self;
+        }
+
+        if let Ok(result) = self
+            .network
+            .get_provider()
+            .create_access_list(&self.request, None)
+            .await
+        {
+            // the default Legacy variant silently drops `set_access_list`; promote
+            // it to the EIP-2930 envelope (valid with or without 1559 fees) so the
+            // list actually rides along on the outgoing tx and into simulation
+            if let TypedTransaction::Legacy(tx) = &self.request {
+                let tx = tx.clone();
+                self.request =
+                    TypedTransaction::Eip2930(Eip2930TransactionRequest::new(tx, Default::default()));
+            }
+
+            self.request.set_access_list(result.access_list);
+            self.access_list_gas = Some(result.gas_used);
+        }
+
+        self
+    }
+
+    /// Populates EIP-1559 fee fields when the target network supports them.
+    ///
+    /// Pulls `eth_feeHistory` for the last few blocks, derives
+    /// `max_priority_fee_per_gas` from the median of the per-block rewards and
+    /// `max_fee_per_gas` from the next block's base fee. Networks that report no
+    /// base fee (or where the call fails) are left untouched and keep
+    /// `gas_price`-based pricing (legacy, or EIP-2930 when an access list was
+    /// prefetched).
+    pub async fn estimate_fees(&mut self) -> &mut SendTransaction {
+        if let Some((max_fee_per_gas, max_priority_fee_per_gas)) = self.eip1559_fees().await {
+            let mut tx = self.as_eip1559();
+            tx.max_fee_per_gas = Some(max_fee_per_gas);
+            tx.max_priority_fee_per_gas = Some(max_priority_fee_per_gas);
+            self.request = TypedTransaction::Eip1559(tx);
+        }
+
+        self
+    }
+
+    /// Upgrades the inner request to its EIP-1559 form, carrying *every* field
+    /// across — including the prefetched access list, nonce and chain id —
+    /// rather than hand-copying a subset and trusting downstream middleware to
+    /// refill the rest.
+    fn as_eip1559(&self) -> Eip1559TransactionRequest {
+        match &self.request {
+            TypedTransaction::Eip1559(tx) => tx.clone(),
+            TypedTransaction::Eip2930(tx) => Eip1559TransactionRequest {
+                from: tx.tx.from,
+                to: tx.tx.to.clone(),
+                gas: tx.tx.gas,
+                value: tx.tx.value,
+                data: tx.tx.data.clone(),
+                nonce: tx.tx.nonce,
+                chain_id: tx.tx.chain_id,
+                access_list: tx.access_list.clone(),
+                max_priority_fee_per_gas: None,
+                max_fee_per_gas: None,
+            },
+            TypedTransaction::Legacy(tx) => Eip1559TransactionRequest {
+                from: tx.from,
+                to: tx.to.clone(),
+                gas: tx.gas,
+                value: tx.value,
+                data: tx.data.clone(),
+                nonce: tx.nonce,
+                chain_id: tx.chain_id,
+                access_list: Default::default(),
+                max_priority_fee_per_gas: None,
+                max_fee_per_gas: None,
+            },
+        }
+    }
+
+    /// Computes `(max_fee_per_gas, max_priority_fee_per_gas)` from `eth_feeHistory`.
+    ///
+    /// Returns `None` when the node doesn't report a base fee (pre-London chains)
+    /// or the call errors, signalling the caller to fall back to legacy pricing.
+    async fn eip1559_fees(&self) -> Option<(U256, U256)> {
+        let history = self
             .network
             .get_provider()
-            .estimate_gas(&self.request, None)
+            .fee_history(10u64, BlockNumber::Latest, &[50.0])
             .await
-            .unwrap_or(1_000_000.into());
+            .ok()?;
+
+        // last entry is the projected base fee for the next block
+        let next_base_fee = history
+            .base_fee_per_gas
+            .last()
+            .copied()
+            .filter(|fee| !fee.is_zero())?;
+
+        let mut rewards: Vec<U256> = history
+            .reward
+            .iter()
+            .filter_map(|block| block.first().copied())
+            .collect();
+        rewards.sort();
+
+        let max_priority_fee_per_gas = rewards
+            .get(rewards.len() / 2)
+            .copied()
+            .unwrap_or_default();
+
+        let max_fee_per_gas = next_base_fee * 2 + max_priority_fee_per_gas;
+
+        Some((max_fee_per_gas, max_priority_fee_per_gas))
+    }
+
+    /// Builds the gas oracle from settings: an external HTTP oracle (when one is
+    /// configured) fronting the node, otherwise the node on its own.
+    async fn gas_oracle(&self) -> Box<dyn GasOracle> {
+        let node = Box::new(NodeOracle {
+            provider: self.network.get_provider(),
+        });
+
+        match Settings::read().await.gas_oracle_url() {
+            Some(url) => Box::new(FallbackOracle {
+                primary: Box::new(HttpOracle { url }),
+                fallback: node,
+            }),
+            None => node,
+        }
+    }
+
+    /// Consults the configured gas oracle, stores the returned tiers and applies
+    /// the currently selected tier to the request's fee fields.
+    pub async fn estimate_gas_tiers(&mut self) -> &mut SendTransaction {
+        if let Some(tiers) = self.gas_oracle().await.estimate().await {
+            self.gas_tiers = Some(tiers);
+            self.apply_gas_tier();
+        }
 
-        self.request.set_gas(gas_limit * 120 / 100);
         self
     }
 
+    /// Applies the selected tier's price to the request's fee fields.
+    ///
+    /// On EIP-1559 the tier sets `max_fee_per_gas` and carries the priority tip
+    /// computed by [`estimate_fees`] along, clamping it to the chosen max so the
+    /// result stays valid (`max_fee >= max_priority_fee`). On legacy chains the
+    /// tier simply becomes the `gas_price`.
+    fn apply_gas_tier(&mut self) {
+        let Some(tiers) = self.gas_tiers else {
+            return;
+        };
+        let price = tiers.get(self.gas_tier);
+
+        if let Some(tx) = self.request.as_eip1559_mut() {
+            let max_priority = tx.max_priority_fee_per_gas.unwrap_or(price).min(price);
+            tx.max_fee_per_gas = Some(price);
+            tx.max_priority_fee_per_gas = Some(max_priority);
+        } else {
+            self.request.set_gas_price(price);
+        }
+    }
+
     pub async fn finish(&mut self) -> Result<PendingTransaction<'_, RetryClient<Http>>> {
+        self.prefetch_access_list().await;
+        // re-estimate after the prefetch so the access list's reported `gasUsed`
+        // feeds the gas limit instead of the flat 120% bump
+        self.estimate_gas().await;
+        self.estimate_fees().await;
+        self.estimate_gas_tiers().await;
+
         // inner scope so as not to lock wallets for the entire duration of the tx review
         let skip = {
             let wallets = Wallets::read().await;
@@ -69,6 +400,17 @@ impl<'a> SendTransaction {
         params["chainId"] = self.network.chain_id.into();
         params["walletType"] = self.wallet_type.to_string().into();
 
+        // let the user pick a confirmation-speed tier from the oracle's prices
+        if let Some(tiers) = self.gas_tiers {
+            params["gasTiers"] = serde_json::to_value(tiers).unwrap();
+        }
+
+        // Ledgers reject opaque calldata unless blind signing is enabled
+        // on-device; warn the user when the request is a contract call
+        if self.is_ledger() {
+            params["blindSigning"] = self.is_contract_call().into();
+        }
+
         let dialog = Dialog::new("tx-review", params);
         dialog.open().await?;
 
@@ -93,7 +435,13 @@ impl<'a> SendTransaction {
         }
 
         if self.is_ledger() {
-            dialog.send("check-ledger", None).await?;
+            // distinct state when calldata is present so the frontend can tell
+            // the user to enable blind signing before approving on-device
+            if self.is_contract_call() {
+                dialog.send("check-ledger-blind-sign", None).await?;
+            } else {
+                dialog.send("check-ledger", None).await?;
+            }
         }
 
         let tx = self.send().await?;
@@ -110,6 +458,14 @@ impl<'a> SendTransaction {
             let v = StringifiedNumeric::String(value.to_string());
             self.request.set_value(U256::try_from(v).unwrap());
         }
+
+        // a tier change re-prices the request from the already-fetched tiers
+        if let Some(tier) = data["tier"].as_str() {
+            if let Ok(tier) = serde_json::from_value(tier.into()) {
+                self.gas_tier = tier;
+                self.apply_gas_tier();
+            }
+        }
     }
 
     async fn simulate(&self, dialog: &Dialog) -> Result<()> {
@@ -127,9 +483,106 @@ impl<'a> SendTransaction {
 
     async fn send(&mut self) -> Result<PendingTransaction<'_, RetryClient<Http>>> {
         self.build_signer().await?;
+
+        // Fill the nonce through the middleware stack and sign exactly once, so
+        // we capture both the exact nonce the `NonceManager` assigned and the
+        // resulting tx hash while only prompting a hardware wallet a single
+        // time. We then broadcast the raw signed bytes rather than handing the
+        // request to `send_transaction`, which would sign it a second time.
+        // Done before broadcast to avoid re-borrowing `self` while the returned
+        // `PendingTransaction` (which borrows the signer) is still alive.
+        let (signed, hash) = {
+            let signer = self.signer.as_ref().unwrap();
+            let signature: Result<Signature> =
+                match signer.fill_transaction(&mut self.request, None).await {
+                    Ok(()) => signer
+                        .signer()
+                        .sign_transaction(&self.request)
+                        .await
+                        .map_err(|_| Error::Signing),
+                    Err(err) => Err(err.into()),
+                };
+            match signature {
+                Ok(signature) => {
+                    (self.request.rlp_signed(&signature), self.request.hash(&signature))
+                }
+                Err(err) => {
+                    // the nonce manager may already have advanced its counter
+                    self.drop_nonce_manager().await;
+                    return Err(err);
+                }
+            }
+        };
+        self.track_pending(hash);
+
         let signer = self.signer.as_ref().unwrap();
+        match signer.send_raw_transaction(signed).await {
+            Ok(tx) => {
+                ethui_broadcast::ui_notify(UINotify::TxsUpdated).await;
+                Ok(tx)
+            }
+            Err(err) => {
+                // drop the cached nonce manager so the next attempt resyncs the
+                // nonce from the node rather than reusing a stale local value
+                self.drop_nonce_manager().await;
+                Err(err.into())
+            }
+        }
+    }
+
+    /// Records the just-broadcast transaction so it can be replaced later.
+    fn track_pending(&mut self, hash: H256) {
+        self.pending = self.request.nonce().copied().map(|nonce| PendingTx {
+            hash,
+            nonce,
+            max_fee_per_gas: self.request.as_eip1559_ref().and_then(|tx| tx.max_fee_per_gas),
+            max_priority_fee_per_gas: self
+                .request
+                .as_eip1559_ref()
+                .and_then(|tx| tx.max_priority_fee_per_gas),
+            gas_price: self.request.gas_price(),
+        });
+    }
+
+    /// Rebroadcasts the pending transaction at the same nonce with escalated
+    /// fees, replacing it with a faster copy.
+    pub async fn speed_up(&mut self) -> Result<PendingTransaction<'_, RetryClient<Http>>> {
+        let pending = self.pending.clone().ok_or(Error::NoPendingTx)?;
+
+        self.request.set_nonce(pending.nonce);
+        self.escalate_fees();
+
+        self.send().await
+    }
 
-        Ok(signer.send_transaction(self.request.clone(), None).await?)
+    /// Cancels the pending transaction by sending a 0-value self-transfer at the
+    /// same nonce with escalated fees, which the node prefers over the original.
+    pub async fn cancel(&mut self) -> Result<PendingTransaction<'_, RetryClient<Http>>> {
+        let pending = self.pending.clone().ok_or(Error::NoPendingTx)?;
+        let from = self.from().await?.to_ethers();
+
+        let mut request = self.request.clone();
+        request.set_to(from);
+        request.set_value(U256::zero());
+        request.set_data(Bytes::new());
+        request.set_nonce(pending.nonce);
+        self.request = request;
+        self.escalate_fees();
+
+        self.send().await
+    }
+
+    /// Multiplies every populated fee field by [`REPLACEMENT_BUMP`], the minimum
+    /// increase nodes require before they'll accept a replacement transaction.
+    fn escalate_fees(&mut self) {
+        let (num, den) = REPLACEMENT_BUMP;
+
+        if let Some(tx) = self.request.as_eip1559_mut() {
+            tx.max_fee_per_gas = tx.max_fee_per_gas.map(|fee| fee * num / den);
+            tx.max_priority_fee_per_gas = tx.max_priority_fee_per_gas.map(|fee| fee * num / den);
+        } else if let Some(gas_price) = self.request.gas_price() {
+            self.request.set_gas_price(gas_price * num / den);
+        }
     }
 
     async fn build_signer(&mut self) -> Result<()> {
@@ -147,11 +600,38 @@ impl<'a> SendTransaction {
             .build_signer(self.network.chain_id, &self.wallet_path)
             .await?;
 
-        let signer = SignerMiddleware::new(self.network.get_provider(), signer);
+        let provider = self.nonce_manager(signer.address()).await;
+        let signer = SignerMiddleware::new(provider, signer);
         self.signer = Some(signer);
         Ok(())
     }
 
+    /// Fetches (or lazily creates) the shared nonce manager for this send's
+    /// `(address, chain_id)`, so concurrent in-flight txs from the same account
+    /// draw from a single monotonically increasing counter.
+    async fn nonce_manager(&self, address: H160) -> Arc<NonceProvider> {
+        let key = (address.to_alloy(), self.network.chain_id);
+
+        NONCE_MANAGERS
+            .lock()
+            .await
+            .entry(key)
+            .or_insert_with(|| {
+                Arc::new(NonceManagerMiddleware::new(
+                    self.network.get_provider(),
+                    address,
+                ))
+            })
+            .clone()
+    }
+
+    async fn drop_nonce_manager(&self) {
+        if let Ok(address) = self.from().await {
+            let key = (address, self.network.chain_id);
+            NONCE_MANAGERS.lock().await.remove(&key);
+        }
+    }
+
     async fn simulation_request(&self) -> Result<ethui_simulator::Request> {
         let tx_request = self.request.clone();
 
@@ -175,6 +655,8 @@ impl<'a> SendTransaction {
                 .map(|v| v.as_u64())
                 .ok_or(())
                 .map_err(|_| Error::CannotSimulate)?,
+            // run the simulation against the same warmed slots the node reported
+            access_list: tx_request.access_list().cloned(),
         })
     }
 
@@ -193,6 +675,15 @@ impl<'a> SendTransaction {
     fn is_ledger(&self) -> bool {
         self.wallet_type == WalletType::Ledger
     }
+
+    /// Whether the request carries calldata, i.e. it's a contract call rather
+    /// than a plain value transfer.
+    fn is_contract_call(&self) -> bool {
+        self.request
+            .data()
+            .map(|data| !data.0.is_empty())
+            .unwrap_or(false)
+    }
 }
 
 pub struct SendTransactionBuilder<'a> {
@@ -271,6 +762,10 @@ impl<'a> SendTransactionBuilder<'a> {
             wallet_type: self.wallet_type.unwrap(),
             network: self.ctx.network().await,
             request: self.request,
+            access_list_gas: None,
+            pending: None,
+            gas_tier: GasTier::default(),
+            gas_tiers: None,
             signer: None,
         }
     }